@@ -0,0 +1,152 @@
+//! Multi-codec input/output for the wasm sort pipeline, built on the `image`
+//! crate so each extra container is just another codec feature rather than a
+//! hand-rolled decoder/encoder. PNG keeps its own path in `lib.rs`, since it
+//! alone needs indexed-palette and 16-bit sample support; every other
+//! container decodes/encodes through here, always as 8-bit
+//! Grayscale/GrayscaleAlpha/Rgb/Rgba.
+//!
+//! Each non-PNG codec is gated behind a Cargo feature of the same name
+//! (`jpeg`, `gif`, `webp`, `tiff`, `pnm`) forwarding to the matching `image`
+//! crate feature, so a lean wasm build can opt out of codecs it won't use.
+
+use std::io::Cursor;
+
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat};
+use png::ColorType;
+
+use crate::config::OutputFormat;
+
+/// A decoded non-PNG image, already reduced to the `(buffer, width, height,
+/// color_type)` shape the sort core expects. Always 8-bit; PNG is the only
+/// container this pipeline round-trips at 16-bit or indexed.
+pub struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ColorType,
+    pub buffer: Vec<u8>,
+}
+
+/// Whether `bytes` starts with the PNG signature. PNG is routed through its
+/// own decode path in `lib.rs`; everything else comes through here.
+pub fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'])
+}
+
+/// Decodes any non-PNG container the enabled codec features support,
+/// guessing the concrete format from its magic bytes.
+pub fn decode(bytes: &[u8]) -> Result<DecodedImage> {
+    let format = image::guess_format(bytes)?;
+    ensure_enabled(format)?;
+    let image = image::load_from_memory_with_format(bytes, format)?;
+    Ok(from_dynamic(image))
+}
+
+/// Re-encodes the sorted buffer as `format`. Only called for non-PNG output
+/// formats; PNG output is handled by the caller so it can carry a palette.
+pub fn encode(
+    format: OutputFormat,
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    buffer: &[u8],
+) -> Result<Vec<u8>> {
+    let image = to_dynamic(width, height, color_type, buffer)?;
+    let image_format = match format {
+        OutputFormat::Png => unreachable!("PNG output is encoded directly in pngsort_main"),
+        #[cfg(feature = "jpeg")]
+        OutputFormat::Jpeg => ImageFormat::Jpeg,
+        #[cfg(feature = "gif")]
+        OutputFormat::Gif => ImageFormat::Gif,
+        #[cfg(feature = "webp")]
+        OutputFormat::WebP => ImageFormat::WebP,
+        #[cfg(feature = "tiff")]
+        OutputFormat::Tiff => ImageFormat::Tiff,
+        #[cfg(feature = "pnm")]
+        OutputFormat::Pnm => ImageFormat::Pnm,
+    };
+
+    let mut out = Cursor::new(Vec::new());
+    image.write_to(&mut out, image_format)?;
+    Ok(out.into_inner())
+}
+
+fn from_dynamic(image: DynamicImage) -> DecodedImage {
+    let width = image.width();
+    let height = image.height();
+    match image {
+        DynamicImage::ImageLuma8(buf) => DecodedImage {
+            width,
+            height,
+            color_type: ColorType::Grayscale,
+            buffer: buf.into_raw(),
+        },
+        DynamicImage::ImageLumaA8(buf) => DecodedImage {
+            width,
+            height,
+            color_type: ColorType::GrayscaleAlpha,
+            buffer: buf.into_raw(),
+        },
+        DynamicImage::ImageRgb8(buf) => DecodedImage {
+            width,
+            height,
+            color_type: ColorType::Rgb,
+            buffer: buf.into_raw(),
+        },
+        other => DecodedImage {
+            width,
+            height,
+            color_type: ColorType::Rgba,
+            buffer: other.to_rgba8().into_raw(),
+        },
+    }
+}
+
+fn to_dynamic(
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    buffer: &[u8],
+) -> Result<DynamicImage> {
+    let buffer = buffer.to_vec();
+    Ok(match color_type {
+        ColorType::Grayscale => DynamicImage::ImageLuma8(
+            image::GrayImage::from_raw(width, height, buffer)
+                .expect("buffer sized for width*height*1"),
+        ),
+        ColorType::GrayscaleAlpha => DynamicImage::ImageLumaA8(
+            image::GrayAlphaImage::from_raw(width, height, buffer)
+                .expect("buffer sized for width*height*2"),
+        ),
+        ColorType::Rgb => DynamicImage::ImageRgb8(
+            image::RgbImage::from_raw(width, height, buffer)
+                .expect("buffer sized for width*height*3"),
+        ),
+        ColorType::Rgba => DynamicImage::ImageRgba8(
+            image::RgbaImage::from_raw(width, height, buffer)
+                .expect("buffer sized for width*height*4"),
+        ),
+        ColorType::Indexed => anyhow::bail!("Indexed images can only be output as PNG"),
+    })
+}
+
+fn ensure_enabled(format: ImageFormat) -> Result<()> {
+    let enabled = match format {
+        #[cfg(feature = "jpeg")]
+        ImageFormat::Jpeg => true,
+        #[cfg(feature = "gif")]
+        ImageFormat::Gif => true,
+        #[cfg(feature = "webp")]
+        ImageFormat::WebP => true,
+        #[cfg(feature = "tiff")]
+        ImageFormat::Tiff => true,
+        #[cfg(feature = "pnm")]
+        ImageFormat::Pnm => true,
+        _ => false,
+    };
+    anyhow::ensure!(
+        enabled,
+        "Input format {format:?} is not enabled in this build"
+    );
+    Ok(())
+}
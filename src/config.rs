@@ -1,5 +1,5 @@
 use anyhow::Result;
-use png::ColorType;
+use png::{BitDepth, ColorType};
 use serde::Deserialize;
 
 #[derive(Deserialize, clap::ValueEnum, Clone, Copy, Debug)]
@@ -22,18 +22,68 @@ pub enum ColorChannel {
     R,
     G,
     B,
+    A,
 }
 
 impl ColorChannel {
-    pub const fn index(&self) -> usize {
+    /// Sample offset of this channel within a pixel of `color_type`. `A` is
+    /// the only channel valid on a Grayscale+alpha pixel, where it sits right
+    /// after the single gray sample rather than after three color samples.
+    pub const fn index(&self, color_type: ColorType) -> usize {
         match self {
             ColorChannel::R => 0,
             ColorChannel::G => 1,
             ColorChannel::B => 2,
+            ColorChannel::A => match color_type {
+                ColorType::GrayscaleAlpha => 1,
+                _ => 3,
+            },
         }
     }
 }
 
+/// A perceptual ordering of an RGB color, used in place of the raw channel
+/// sum/composite key from `sort_mode`.
+#[derive(Deserialize, clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    /// Rec. 709 luma: `0.2126*R + 0.7152*G + 0.0722*B`.
+    Luminance,
+    /// HSV hue, in degrees around the color wheel.
+    Hue,
+    /// HSV saturation: chroma relative to the brightest channel.
+    Saturation,
+}
+
+/// Output container format for the wasm sort pipeline. PNG is lossless and
+/// always available; the rest are optional codecs, each gated behind a
+/// same-named Cargo feature, so a minimal wasm build doesn't pull in codecs
+/// it won't use.
+#[derive(Deserialize, clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    #[cfg(feature = "jpeg")]
+    Jpeg,
+    #[cfg(feature = "gif")]
+    Gif,
+    #[cfg(feature = "webp")]
+    WebP,
+    #[cfg(feature = "tiff")]
+    Tiff,
+    #[cfg(feature = "pnm")]
+    Pnm,
+}
+
+/// How to emit a sorted indexed (palette) image.
+#[derive(Deserialize, clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IndexedOutput {
+    /// Reorder index bytes and keep the image indexed; the palette is unchanged.
+    #[default]
+    Indexed,
+    /// Resolve each index through the palette and emit full RGB/RGBA samples.
+    Expand,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Config {
     #[serde(default)]
@@ -42,10 +92,29 @@ pub struct Config {
     pub sort_mode: Option<SortMode>,
     #[serde(default)]
     pub sort_channel: Vec<ColorChannel>,
+    /// Sort by a perceptual property of the RGB color instead of `sort_mode`'s
+    /// channel sum/composite key. Mutually exclusive with `sort_mode`.
+    pub sort_key: Option<SortKey>,
+    /// How to emit a sorted indexed (palette) image. Ignored for other color types.
+    #[serde(default)]
+    pub indexed_output: IndexedOutput,
+    /// Container format to re-encode the sorted image as. Defaults to PNG,
+    /// which is also the only format an indexed output can be emitted as.
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    /// Lower bound (inclusive) of the sort-key window for threshold/interval sorting.
+    /// Only pixels whose key falls in `[threshold_lo, threshold_hi]` are sorted;
+    /// the rest stay fixed as boundaries. Only applies to Row/Column sort ranges.
+    pub threshold_lo: Option<u64>,
+    /// Upper bound (inclusive) of the sort-key window. See `threshold_lo`.
+    pub threshold_hi: Option<u64>,
+    /// Sort the pixels outside `[threshold_lo, threshold_hi]` instead of inside it.
+    #[serde(default)]
+    pub invert_threshold: bool,
 }
 
 impl Config {
-    pub fn validate(&self, color_type: ColorType) -> Result<()> {
+    pub fn validate(&self, color_type: ColorType, bit_depth: BitDepth) -> Result<()> {
         let mut sorted_channels = self.sort_channel.clone();
         sorted_channels.sort();
         sorted_channels.dedup();
@@ -54,24 +123,116 @@ impl Config {
         }
 
         match color_type {
-            ColorType::Rgb | ColorType::Rgba => {
+            ColorType::Rgb => {
+                if self.sort_channel.contains(&ColorChannel::A) {
+                    anyhow::bail!("Rgb images have no alpha channel; remove A from sort_channel");
+                }
+                if let Some(SortMode::Untied) = self.sort_mode
+                    && self.sort_channel.is_empty()
+                {
+                    anyhow::bail!("Sort channel should be specified when using Untied sort mode");
+                }
+            }
+            ColorType::Rgba => {
                 if let Some(SortMode::Untied) = self.sort_mode
                     && self.sort_channel.is_empty()
                 {
                     anyhow::bail!("Sort channel should be specified when using Untied sort mode");
                 }
             }
-            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+            ColorType::Grayscale => {
                 if self.sort_mode.is_some() {
                     anyhow::bail!("Sort mode option is not applicable for Grayscale images");
                 }
                 if !self.sort_channel.is_empty() {
                     anyhow::bail!("Channel option is not applicable for Grayscale images");
                 }
+                if self.sort_key.is_some() {
+                    anyhow::bail!("sort_key is not applicable for Grayscale images");
+                }
+            }
+            ColorType::GrayscaleAlpha => {
+                if self.sort_key.is_some() {
+                    anyhow::bail!("sort_key is not applicable for Grayscale images");
+                }
+                if self.sort_channel.iter().any(|c| *c != ColorChannel::A) {
+                    anyhow::bail!(
+                        "GrayscaleAlpha images only support the A channel in sort_channel"
+                    );
+                }
+                if let Some(SortMode::Untied) = self.sort_mode
+                    && self.sort_channel.is_empty()
+                {
+                    anyhow::bail!("Sort channel should be specified when using Untied sort mode");
+                }
+            }
+            ColorType::Indexed => {
+                if self.sort_channel.contains(&ColorChannel::A) {
+                    anyhow::bail!(
+                        "Indexed images have no alpha channel; remove A from sort_channel"
+                    );
+                }
+                if let Some(SortMode::Untied) = self.sort_mode {
+                    anyhow::bail!("Untied sort mode is not applicable for Indexed images");
+                }
+            }
+        }
+
+        if self.sort_key.is_some() {
+            if self.sort_mode.is_some() {
+                anyhow::bail!("sort_key and sort_mode are mutually exclusive");
+            }
+            if !self.sort_channel.is_empty() {
+                anyhow::bail!("sort_channel is not applicable when sort_key is set");
+            }
+        }
+
+        match bit_depth {
+            BitDepth::One | BitDepth::Two | BitDepth::Four => {
+                anyhow::bail!(
+                    "Bit depths below 8 ({:?}) are not supported; expand the image to 8-bit first",
+                    bit_depth
+                );
             }
-            ColorType::Indexed => anyhow::bail!("Indexed color type is not supported"),
+            BitDepth::Eight | BitDepth::Sixteen => {}
+        }
+
+        if self.threshold_lo.is_some() != self.threshold_hi.is_some() {
+            anyhow::bail!("Both threshold_lo and threshold_hi must be provided together");
+        }
+        if let (Some(lo), Some(hi)) = (self.threshold_lo, self.threshold_hi) {
+            if lo > hi {
+                anyhow::bail!("threshold_lo must not exceed threshold_hi");
+            }
+            if matches!(self.sort_range, SortRange::RowMajor | SortRange::ColumnMajor) {
+                anyhow::bail!("Threshold sorting is only supported for Row and Column sort ranges");
+            }
+            if self.sort_mode == Some(SortMode::Untied) {
+                anyhow::bail!("threshold_lo/threshold_hi are not applicable for Untied sort mode");
+            }
+        }
+
+        if self.output_format != OutputFormat::Png
+            && color_type == ColorType::Indexed
+            && self.indexed_output == IndexedOutput::Indexed
+        {
+            anyhow::bail!(
+                "Indexed output can only be emitted as PNG; set indexed_output to \
+                 Expand or output_format to Png"
+            );
+        }
+
+        if self.output_format != OutputFormat::Png && matches!(bit_depth, BitDepth::Sixteen) {
+            anyhow::bail!(
+                "16-bit images can only be emitted as PNG; the image crate's other codecs \
+                 only support 8-bit samples"
+            );
         }
 
         Ok(())
     }
+
+    pub fn threshold(&self) -> Option<(u64, u64)> {
+        self.threshold_lo.zip(self.threshold_hi)
+    }
 }
@@ -0,0 +1,345 @@
+use anyhow::Result;
+use png::{BitDepth, ColorType};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// A decoded image in a common, format-agnostic representation that the sort
+/// core operates on. `buffer` is laid out exactly as PNG would decode it:
+/// row-major, `color_type`/`bit_depth` sized pixels. `palette`/`trns` are only
+/// ever populated by the PNG backend.
+pub struct ImageBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub color_type: ColorType,
+    pub bit_depth: BitDepth,
+    pub buffer: Vec<u8>,
+    pub palette: Option<Vec<u8>>,
+    pub trns: Option<Vec<u8>>,
+}
+
+/// Image container backends the sort pipeline can read and write. Selected by
+/// file extension unless overridden with `--format`.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Tiff,
+    Bmp,
+}
+
+impl Format {
+    /// Infers the format from a file's extension.
+    pub fn from_path(path: &str) -> Result<Self> {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Ok(Format::Png),
+            "tif" | "tiff" => Ok(Format::Tiff),
+            "bmp" => Ok(Format::Bmp),
+            other => anyhow::bail!(
+                "Cannot infer image format from extension {other:?}; pass --format explicitly"
+            ),
+        }
+    }
+
+    pub fn decode(self, path: &str) -> Result<ImageBuffer> {
+        match self {
+            Format::Png => decode_png(path),
+            Format::Tiff => decode_tiff(path),
+            Format::Bmp => decode_bmp(path),
+        }
+    }
+
+    pub fn encode(self, path: &str, image: &ImageBuffer) -> Result<()> {
+        match self {
+            Format::Png => encode_png(path, image),
+            Format::Tiff => encode_tiff(path, image),
+            Format::Bmp => encode_bmp(path, image),
+        }
+    }
+}
+
+fn decode_png(path: &str) -> Result<ImageBuffer> {
+    let file = File::open(path)?;
+    let decoder = png::Decoder::new(BufReader::new(file));
+    let mut reader = decoder.read_info()?;
+    let info = reader.info();
+
+    let color_type = info.color_type;
+    let bit_depth = info.bit_depth;
+    let width = info.width as usize;
+    let height = info.height as usize;
+    let palette = info.palette.as_ref().map(|p| p.to_vec());
+    let trns = info.trns.as_ref().map(|t| t.to_vec());
+
+    let mut buffer = vec![0; reader.output_buffer_size().unwrap()];
+    reader.next_frame(&mut buffer)?;
+
+    Ok(ImageBuffer {
+        width,
+        height,
+        color_type,
+        bit_depth,
+        buffer,
+        palette,
+        trns,
+    })
+}
+
+fn encode_png(path: &str, image: &ImageBuffer) -> Result<()> {
+    let file = File::create(path)?;
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(file), image.width as u32, image.height as u32);
+    encoder.set_color(image.color_type);
+    encoder.set_depth(image.bit_depth);
+    if let Some(palette) = &image.palette {
+        encoder.set_palette(palette.clone());
+    }
+    if let Some(trns) = &image.trns {
+        encoder.set_trns(trns.clone());
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.buffer)?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn decode_tiff(path: &str) -> Result<ImageBuffer> {
+    use tiff::ColorType as TiffColorType;
+    use tiff::decoder::{Decoder, DecodingResult};
+
+    let file = File::open(path)?;
+    let mut decoder = Decoder::new(BufReader::new(file))?;
+    let (width, height) = decoder.dimensions()?;
+    let tiff_color = decoder.colortype()?;
+    let decoded = decoder.read_image()?;
+
+    let (color_type, bit_depth, buffer) = match (tiff_color, decoded) {
+        (TiffColorType::Gray(8), DecodingResult::U8(data)) => {
+            (ColorType::Grayscale, BitDepth::Eight, data)
+        }
+        (TiffColorType::GrayA(8), DecodingResult::U8(data)) => {
+            (ColorType::GrayscaleAlpha, BitDepth::Eight, data)
+        }
+        (TiffColorType::RGB(8), DecodingResult::U8(data)) => {
+            (ColorType::Rgb, BitDepth::Eight, data)
+        }
+        (TiffColorType::RGBA(8), DecodingResult::U8(data)) => {
+            (ColorType::Rgba, BitDepth::Eight, data)
+        }
+        (TiffColorType::Gray(16), DecodingResult::U16(data)) => {
+            (ColorType::Grayscale, BitDepth::Sixteen, u16_to_be_bytes(&data))
+        }
+        (TiffColorType::GrayA(16), DecodingResult::U16(data)) => {
+            (ColorType::GrayscaleAlpha, BitDepth::Sixteen, u16_to_be_bytes(&data))
+        }
+        (TiffColorType::RGB(16), DecodingResult::U16(data)) => {
+            (ColorType::Rgb, BitDepth::Sixteen, u16_to_be_bytes(&data))
+        }
+        (TiffColorType::RGBA(16), DecodingResult::U16(data)) => {
+            (ColorType::Rgba, BitDepth::Sixteen, u16_to_be_bytes(&data))
+        }
+        (other, _) => anyhow::bail!("Unsupported TIFF sample layout: {other:?}"),
+    };
+
+    Ok(ImageBuffer {
+        width: width as usize,
+        height: height as usize,
+        color_type,
+        bit_depth,
+        buffer,
+        palette: None,
+        trns: None,
+    })
+}
+
+fn encode_tiff(path: &str, image: &ImageBuffer) -> Result<()> {
+    use tiff::encoder::{TiffEncoder, colortype};
+
+    let file = File::create(path)?;
+    let mut encoder = TiffEncoder::new(BufWriter::new(file))?;
+    let (width, height) = (image.width as u32, image.height as u32);
+
+    match (image.color_type, image.bit_depth) {
+        (ColorType::Grayscale, BitDepth::Eight) => {
+            encoder.write_image::<colortype::Gray8>(width, height, &image.buffer)?;
+        }
+        (ColorType::GrayscaleAlpha, BitDepth::Eight) => {
+            encoder.write_image::<colortype::GrayA8>(width, height, &image.buffer)?;
+        }
+        (ColorType::Rgb, BitDepth::Eight) => {
+            encoder.write_image::<colortype::RGB8>(width, height, &image.buffer)?;
+        }
+        (ColorType::Rgba, BitDepth::Eight) => {
+            encoder.write_image::<colortype::RGBA8>(width, height, &image.buffer)?;
+        }
+        (ColorType::Grayscale, BitDepth::Sixteen) => {
+            let samples = be_bytes_to_u16(&image.buffer);
+            encoder.write_image::<colortype::Gray16>(width, height, &samples)?;
+        }
+        (ColorType::GrayscaleAlpha, BitDepth::Sixteen) => {
+            let samples = be_bytes_to_u16(&image.buffer);
+            encoder.write_image::<colortype::GrayA16>(width, height, &samples)?;
+        }
+        (ColorType::Rgb, BitDepth::Sixteen) => {
+            let samples = be_bytes_to_u16(&image.buffer);
+            encoder.write_image::<colortype::RGB16>(width, height, &samples)?;
+        }
+        (ColorType::Rgba, BitDepth::Sixteen) => {
+            let samples = be_bytes_to_u16(&image.buffer);
+            encoder.write_image::<colortype::RGBA16>(width, height, &samples)?;
+        }
+        (color_type, bit_depth) => {
+            anyhow::bail!("TIFF output does not support {color_type:?} at {bit_depth:?}")
+        }
+    }
+
+    Ok(())
+}
+
+fn u16_to_be_bytes(samples: &[u16]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(samples.len() * 2);
+    for sample in samples {
+        out.extend_from_slice(&sample.to_be_bytes());
+    }
+    out
+}
+
+fn be_bytes_to_u16(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+        .collect()
+}
+
+// A minimal, self-contained reader/writer for uncompressed 24-bit BGR BMP
+// files (the common "simple" BMP variant). No palette or compression support.
+
+const BMP_FILE_HEADER_SIZE: usize = 14;
+const BMP_DIB_HEADER_SIZE: usize = 40;
+
+fn decode_bmp(path: &str) -> Result<ImageBuffer> {
+    let mut file = File::open(path)?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+
+    anyhow::ensure!(
+        data.len() >= BMP_FILE_HEADER_SIZE + BMP_DIB_HEADER_SIZE,
+        "BMP file too small"
+    );
+    anyhow::ensure!(&data[0..2] == b"BM", "Not a BMP file");
+
+    let pixel_offset = u32::from_le_bytes(data[10..14].try_into().unwrap()) as usize;
+    let width = i32::from_le_bytes(data[18..22].try_into().unwrap());
+    let height = i32::from_le_bytes(data[22..26].try_into().unwrap());
+    let bits_per_pixel = u16::from_le_bytes(data[28..30].try_into().unwrap());
+    let compression = u32::from_le_bytes(data[30..34].try_into().unwrap());
+
+    anyhow::ensure!(compression == 0, "Compressed BMP files are not supported");
+    anyhow::ensure!(
+        bits_per_pixel == 24,
+        "Only 24-bit uncompressed BMP files are supported"
+    );
+
+    let width = width.unsigned_abs() as usize;
+    let top_down = height < 0;
+    let height = height.unsigned_abs() as usize;
+
+    let row_stride = (width * 3).div_ceil(4) * 4;
+    let mut buffer = vec![0u8; width * height * 3];
+
+    for row in 0..height {
+        let src_row = if top_down { row } else { height - 1 - row };
+        let row_start = pixel_offset + src_row * row_stride;
+        for col in 0..width {
+            let src = row_start + col * 3;
+            let dst = (row * width + col) * 3;
+            // BMP stores BGR; the sort pipeline works in RGB.
+            buffer[dst] = data[src + 2];
+            buffer[dst + 1] = data[src + 1];
+            buffer[dst + 2] = data[src];
+        }
+    }
+
+    Ok(ImageBuffer {
+        width,
+        height,
+        color_type: ColorType::Rgb,
+        bit_depth: BitDepth::Eight,
+        buffer,
+        palette: None,
+        trns: None,
+    })
+}
+
+fn encode_bmp(path: &str, image: &ImageBuffer) -> Result<()> {
+    anyhow::ensure!(
+        image.bit_depth == BitDepth::Eight,
+        "BMP output only supports 8-bit samples"
+    );
+
+    let (width, height) = (image.width, image.height);
+    let row_stride = (width * 3).div_ceil(4) * 4;
+    let pixel_data_size = row_stride * height;
+    let file_size = BMP_FILE_HEADER_SIZE + BMP_DIB_HEADER_SIZE + pixel_data_size;
+
+    let mut out = Vec::with_capacity(file_size);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(file_size as u32).to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+    out.extend_from_slice(&((BMP_FILE_HEADER_SIZE + BMP_DIB_HEADER_SIZE) as u32).to_le_bytes());
+
+    out.extend_from_slice(&(BMP_DIB_HEADER_SIZE as u32).to_le_bytes());
+    out.extend_from_slice(&(width as i32).to_le_bytes());
+    out.extend_from_slice(&(height as i32).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&24u16.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(pixel_data_size as u32).to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&2835i32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+
+    let channels = channels_for(image.color_type);
+    for row in (0..height).rev() {
+        let row_start = out.len();
+        for col in 0..width {
+            let idx = (row * width + col) * channels;
+            let (r, g, b) = match image.color_type {
+                ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                    let v = image.buffer[idx];
+                    (v, v, v)
+                }
+                ColorType::Rgb | ColorType::Rgba => (
+                    image.buffer[idx],
+                    image.buffer[idx + 1],
+                    image.buffer[idx + 2],
+                ),
+                ColorType::Indexed => anyhow::bail!("BMP output does not support indexed images"),
+            };
+            out.push(b);
+            out.push(g);
+            out.push(r);
+        }
+        out.resize(row_start + row_stride, 0);
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&out)?;
+    Ok(())
+}
+
+fn channels_for(color_type: ColorType) -> usize {
+    match color_type {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        ColorType::Indexed => 1,
+    }
+}
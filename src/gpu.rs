@@ -0,0 +1,260 @@
+//! Optional GPU backend: sorts whole rows/columns in parallel with a bitonic
+//! sorting network run entirely inside one compute-shader workgroup per line.
+//!
+//! Because the network lives in workgroup-shared memory with barriers between
+//! stages, a single workgroup invocation is limited to `WORKGROUP_SIZE`
+//! threads, which caps the line length this backend can handle. Callers are
+//! expected to fall back to the CPU path (`sort_by_rows`/`sort_by_columns`)
+//! whenever [`GpuSorter::new`] returns `None` or [`GpuSorter::sort_lines`]
+//! returns `None` (no adapter, or the line is too long for one workgroup).
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use wgpu::util::DeviceExt;
+
+    /// Threads per workgroup; also the longest line this backend can sort,
+    /// since the whole bitonic network for a line runs inside one workgroup.
+    pub const WORKGROUP_SIZE: u32 = 256;
+
+    const SHADER_SRC: &str = include_str!("bitonic_sort.wgsl");
+
+    #[repr(C)]
+    #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+    struct Params {
+        line_len: u32,
+        padded_len: u32,
+        _pad0: u32,
+        _pad1: u32,
+    }
+
+    pub struct GpuSorter {
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        pipeline: wgpu::ComputePipeline,
+        bind_group_layout: wgpu::BindGroupLayout,
+    }
+
+    impl GpuSorter {
+        /// Requests a GPU adapter/device and compiles the bitonic-sort shader.
+        /// Returns `None` if no adapter is available, mirroring the CPU
+        /// fallback the caller is expected to take in that case.
+        pub fn new() -> Option<Self> {
+            let instance = wgpu::Instance::default();
+            let adapter =
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    ..Default::default()
+                }))?;
+            let (device, queue) = pollster::block_on(adapter.request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("pngsort bitonic sort device"),
+                    ..Default::default()
+                },
+                None,
+            ))
+            .ok()?;
+
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("pngsort bitonic sort shader"),
+                source: wgpu::ShaderSource::Wgsl(SHADER_SRC.into()),
+            });
+
+            let bind_group_layout =
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("pngsort bitonic sort bind group layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::COMPUTE,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                        storage_entry(1),
+                        storage_entry(2),
+                        storage_entry(3),
+                    ],
+                });
+
+            let pipeline_layout =
+                device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("pngsort bitonic sort pipeline layout"),
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+
+            let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("pngsort bitonic sort pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "compare_exchange",
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+            Some(Self {
+                device,
+                queue,
+                pipeline,
+                bind_group_layout,
+            })
+        }
+
+        /// Sorts `num_lines` independent lines of `line_len` keys each
+        /// (flattened row-major: `keys[line * line_len + i]`), returning, for
+        /// every output slot, the source index within its line that should be
+        /// gathered there. Returns `None` if `line_len` exceeds the workgroup
+        /// size, so the caller can fall back to the CPU path.
+        pub fn sort_lines(
+            &self,
+            keys: &[u64],
+            num_lines: usize,
+            line_len: usize,
+        ) -> Option<Vec<u32>> {
+            if line_len == 0 || line_len > WORKGROUP_SIZE as usize {
+                return None;
+            }
+            let padded_len = line_len.next_power_of_two() as u32;
+
+            let mut keys_hi = Vec::with_capacity(keys.len());
+            let mut keys_lo = Vec::with_capacity(keys.len());
+            for &key in keys {
+                keys_hi.push((key >> 32) as u32);
+                keys_lo.push(key as u32);
+            }
+
+            let params = Params {
+                line_len: line_len as u32,
+                padded_len,
+                _pad0: 0,
+                _pad1: 0,
+            };
+            let params_buf = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("pngsort bitonic sort params"),
+                    contents: bytemuck::bytes_of(&params),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+            let keys_hi_buf = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("pngsort bitonic sort keys_hi"),
+                    contents: bytemuck::cast_slice(&keys_hi),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+            let keys_lo_buf = self
+                .device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("pngsort bitonic sort keys_lo"),
+                    contents: bytemuck::cast_slice(&keys_lo),
+                    usage: wgpu::BufferUsages::STORAGE,
+                });
+
+            let indices_size = (keys.len() * std::mem::size_of::<u32>()) as u64;
+            let indices_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("pngsort bitonic sort indices"),
+                size: indices_size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let staging_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("pngsort bitonic sort staging"),
+                size: indices_size,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("pngsort bitonic sort bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params_buf.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: keys_hi_buf.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: keys_lo_buf.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: indices_buf.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("pngsort bitonic sort encoder"),
+                });
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("pngsort bitonic sort pass"),
+                    timestamp_writes: None,
+                });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(num_lines as u32, 1, 1);
+            }
+            encoder.copy_buffer_to_buffer(&indices_buf, 0, &staging_buf, 0, indices_size);
+            self.queue.submit(Some(encoder.finish()));
+
+            let slice = staging_buf.slice(..);
+            let (tx, rx) = std::sync::mpsc::channel();
+            slice.map_async(wgpu::MapMode::Read, move |res| {
+                let _ = tx.send(res);
+            });
+            self.device.poll(wgpu::Maintain::Wait);
+            rx.recv().ok()?.ok()?;
+
+            let result: Vec<u32> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+            Some(result)
+        }
+    }
+
+    fn storage_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::GpuSorter;
+
+/// wasm32 has no synchronous way to wait on an adapter/device request, so the
+/// GPU backend is unavailable there and every caller takes the CPU fallback.
+#[cfg(target_arch = "wasm32")]
+pub struct GpuSorter;
+
+#[cfg(target_arch = "wasm32")]
+impl GpuSorter {
+    pub fn new() -> Option<Self> {
+        None
+    }
+
+    pub fn sort_lines(
+        &self,
+        _keys: &[u64],
+        _num_lines: usize,
+        _line_len: usize,
+    ) -> Option<Vec<u32>> {
+        None
+    }
+}
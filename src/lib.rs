@@ -1,10 +1,11 @@
+pub mod codecs;
 pub mod config;
 
-use std::io::{BufRead, BufReader, Cursor, Seek};
+use std::io::{BufRead, BufReader, Cursor, Read, Seek};
 
 use anyhow::Result;
-use config::{Config, SortMode, SortRange};
-use png::ColorType;
+use config::{ColorChannel, Config, IndexedOutput, OutputFormat, SortKey, SortMode, SortRange};
+use png::{BitDepth, ColorType};
 
 use wasm_bindgen::prelude::*;
 
@@ -14,7 +15,7 @@ macro_rules! console_log {
     }
 }
 
-type SortFn = Box<dyn Fn(&&[u8]) -> i32>;
+type SortFn = Box<dyn Fn(&&[u8]) -> u64>;
 
 #[wasm_bindgen]
 pub fn wasm_main(config: &str, src: &[u8]) -> Result<Vec<u8>, JsValue> {
@@ -27,33 +28,84 @@ pub fn wasm_main(config: &str, src: &[u8]) -> Result<Vec<u8>, JsValue> {
     Ok(output_data)
 }
 
-pub fn pngsort_main(config: &Config, src: impl BufRead + Seek) -> Result<Vec<u8>> {
-    let decoder = png::Decoder::new(src);
-    let mut reader = decoder.read_info()?;
-    let info = reader.info();
-
-    let color_type = info.color_type;
-    let bit_depth = info.bit_depth;
-
-    config.validate(color_type)?;
-
-    let width = info.width;
-    let height = info.height;
-    let mut src_buf = vec![0; reader.output_buffer_size().unwrap()];
-    reader.next_frame(&mut src_buf)?;
+pub fn pngsort_main(config: &Config, mut src: impl BufRead + Seek) -> Result<Vec<u8>> {
+    let mut input_buf = Vec::new();
+    src.read_to_end(&mut input_buf)?;
+
+    let (color_type, bit_depth, palette, trns, width, height, src_buf) =
+        if codecs::is_png(&input_buf) {
+            let decoder = png::Decoder::new(Cursor::new(&input_buf));
+            let mut reader = decoder.read_info()?;
+            let info = reader.info();
+
+            let color_type = info.color_type;
+            let bit_depth = info.bit_depth;
+            let palette = info.palette.as_ref().map(|p| p.to_vec());
+            let trns = info.trns.as_ref().map(|t| t.to_vec());
+            let width = info.width;
+            let height = info.height;
+
+            let mut src_buf = vec![0; reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut src_buf)?;
+            (color_type, bit_depth, palette, trns, width, height, src_buf)
+        } else {
+            let decoded = codecs::decode(&input_buf)?;
+            (
+                decoded.color_type,
+                BitDepth::Eight,
+                None,
+                None,
+                decoded.width,
+                decoded.height,
+                decoded.buffer,
+            )
+        };
+
+    config.validate(color_type, bit_depth)?;
+
+    let (output_color_type, sorted_buf, output_palette, output_trns) =
+        if color_type == ColorType::Indexed {
+            let palette = palette.expect("Indexed PNG is missing its PLTE chunk");
+            process_indexed_image(
+                config,
+                &src_buf,
+                width as usize,
+                height as usize,
+                &palette,
+                trns.as_deref(),
+            )?
+        } else {
+            let sorted_buf = process_image(
+                config,
+                &src_buf,
+                width as usize,
+                height as usize,
+                color_type,
+                bit_depth,
+            )?;
+            (color_type, sorted_buf, None, None)
+        };
+
+    if config.output_format != OutputFormat::Png {
+        return codecs::encode(config.output_format, width, height, output_color_type, &sorted_buf);
+    }
 
-    let sorted_buf = process_image(
-        config,
-        &src_buf,
-        width as usize,
-        height as usize,
-        color_type,
-    )?;
+    let output_bit_depth = if color_type == ColorType::Indexed && output_color_type != ColorType::Indexed {
+        BitDepth::Eight
+    } else {
+        bit_depth
+    };
 
     let mut encoded_buf: Vec<u8> = Vec::new();
     let mut encoder = png::Encoder::new(&mut encoded_buf, width, height);
-    encoder.set_color(color_type);
-    encoder.set_depth(bit_depth);
+    encoder.set_color(output_color_type);
+    encoder.set_depth(output_bit_depth);
+    if let Some(palette) = output_palette {
+        encoder.set_palette(palette);
+    }
+    if let Some(trns) = output_trns {
+        encoder.set_trns(trns);
+    }
     let mut writer = encoder.write_header()?;
     writer.write_image_data(&sorted_buf)?;
     writer.finish()?;
@@ -61,20 +113,146 @@ pub fn pngsort_main(config: &Config, src: impl BufRead + Seek) -> Result<Vec<u8>
     Ok(encoded_buf)
 }
 
+/// Sorts an indexed (palette) image by rearranging index bytes keyed by each
+/// entry's resolved RGB color, then either keeps the result indexed (palette
+/// unchanged) or expands it to RGB/RGBA samples per `config.indexed_output`.
+#[allow(clippy::type_complexity)]
+fn process_indexed_image(
+    config: &Config,
+    src_buf: &[u8],
+    width: usize,
+    height: usize,
+    palette: &[u8],
+    trns: Option<&[u8]>,
+) -> Result<(ColorType, Vec<u8>, Option<Vec<u8>>, Option<Vec<u8>>)> {
+    let sort_fn = create_indexed_sort_function(config, palette);
+    let threshold = config.threshold();
+    let mut out_indices = vec![0u8; src_buf.len()];
+
+    match config.sort_range {
+        SortRange::Row => {
+            sort_by_rows(
+                src_buf,
+                &mut out_indices,
+                width,
+                height,
+                1,
+                &sort_fn,
+                config.descending,
+                threshold,
+                config.invert_threshold,
+            );
+        }
+        SortRange::Column => {
+            sort_by_columns(
+                src_buf,
+                &mut out_indices,
+                width,
+                height,
+                1,
+                &sort_fn,
+                config.descending,
+                threshold,
+                config.invert_threshold,
+            );
+        }
+        SortRange::RowMajor => {
+            sort_row_major(src_buf, &mut out_indices, 1, &sort_fn, config.descending);
+        }
+        SortRange::ColumnMajor => {
+            sort_column_major(
+                src_buf,
+                &mut out_indices,
+                width,
+                height,
+                1,
+                &sort_fn,
+                config.descending,
+            );
+        }
+    }
+
+    match config.indexed_output {
+        IndexedOutput::Indexed => Ok((
+            ColorType::Indexed,
+            out_indices,
+            Some(palette.to_vec()),
+            trns.map(|t| t.to_vec()),
+        )),
+        IndexedOutput::Expand => {
+            let channels = if trns.is_some() { 4 } else { 3 };
+            let mut expanded = vec![0u8; out_indices.len() * channels];
+            for (i, &idx) in out_indices.iter().enumerate() {
+                let offset = idx as usize * 3;
+                let dst = i * channels;
+                expanded[dst..dst + 3].copy_from_slice(&palette[offset..offset + 3]);
+                if let Some(trns) = trns {
+                    expanded[dst + 3] = trns.get(idx as usize).copied().unwrap_or(255);
+                }
+            }
+            let color_type = if trns.is_some() {
+                ColorType::Rgba
+            } else {
+                ColorType::Rgb
+            };
+            Ok((color_type, expanded, None, None))
+        }
+    }
+}
+
+/// Builds a sort key for an index byte by resolving it through the palette
+/// and reusing the RGB sort function on the resulting color.
+fn create_indexed_sort_function(config: &Config, palette: &[u8]) -> SortFn {
+    let rgb_sort_fn = create_sort_function(config, ColorType::Rgb, 1);
+    let palette = palette.to_vec();
+    Box::new(move |pixel| {
+        let offset = pixel[0] as usize * 3;
+        let entry = &palette[offset..offset + 3];
+        rgb_sort_fn(&entry)
+    })
+}
+
+/// Reads one channel sample (1 or 2 bytes, big-endian) out of a pixel slice.
+fn read_sample(buf: &[u8], offset: usize, bytes_per_sample: usize) -> u16 {
+    if bytes_per_sample == 2 {
+        u16::from_be_bytes([buf[offset], buf[offset + 1]])
+    } else {
+        buf[offset] as u16
+    }
+}
+
+/// Writes one channel sample back as 1 or 2 big-endian bytes.
+fn write_sample(buf: &mut [u8], offset: usize, value: u16, bytes_per_sample: usize) {
+    if bytes_per_sample == 2 {
+        let bytes = value.to_be_bytes();
+        buf[offset] = bytes[0];
+        buf[offset + 1] = bytes[1];
+    } else {
+        buf[offset] = value as u8;
+    }
+}
+
 fn process_image(
     config: &Config,
     src_buf: &[u8],
     width: usize,
     height: usize,
     color_type: ColorType,
+    bit_depth: BitDepth,
 ) -> Result<Vec<u8>> {
-    let bytes_per_pixel = match color_type {
+    let channels = match color_type {
         ColorType::Grayscale => 1,
         ColorType::GrayscaleAlpha => 2,
         ColorType::Rgb => 3,
         ColorType::Rgba => 4,
         ColorType::Indexed => unreachable!(),
     };
+    let bytes_per_sample = if matches!(bit_depth, BitDepth::Sixteen) {
+        2
+    } else {
+        1
+    };
+    let bytes_per_pixel = channels * bytes_per_sample;
 
     let mut out_buf = vec![0; src_buf.len()];
 
@@ -86,6 +264,7 @@ fn process_image(
             width,
             height,
             bytes_per_pixel,
+            bytes_per_sample,
             color_type,
         )?;
     } else {
@@ -96,38 +275,112 @@ fn process_image(
             width,
             height,
             bytes_per_pixel,
+            bytes_per_sample,
+            color_type,
         )?;
     }
 
     Ok(out_buf)
 }
 
-fn create_sort_function(config: &Config, color_type: ColorType) -> SortFn {
-    let asc: SortFn = match color_type {
-        ColorType::Grayscale | ColorType::GrayscaleAlpha => Box::new(|pixel| pixel[0] as i32),
-        ColorType::Rgb | ColorType::Rgba => {
-            let channels = config.sort_channel.clone();
-            let mode = config.sort_mode;
+/// Builds the ascending sort key function for a pixel. `descending` is
+/// intentionally *not* applied here; callers fold it in at the point where
+/// pixels are ordered (see `sort_line`) so that threshold windows, which are
+/// documented as bounds on the ascending key, never have to be negated to
+/// match.
+fn create_sort_function(config: &Config, color_type: ColorType, bytes_per_sample: usize) -> SortFn {
+    match color_type {
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+            // The only channel `sort_channel` can hold here is `A` (see
+            // `Config::validate`); when it's set, alpha becomes the key and
+            // gray rides along tied to its pixel, rather than the default of
+            // keying on gray.
+            let key_alpha = config.sort_channel.contains(&ColorChannel::A);
             Box::new(move |pixel| {
-                channels.iter().fold(0i32, |key, channel| {
-                    let idx = channel.index();
-                    match mode {
-                        Some(SortMode::TiedBySum) | None => key + pixel[idx] as i32,
-                        Some(SortMode::TiedByOrder) => (key << 8) | (pixel[idx] as i32),
-                        _ => unreachable!(),
-                    }
-                })
+                let offset = if key_alpha { bytes_per_sample } else { 0 };
+                read_sample(pixel, offset, bytes_per_sample) as u64
             })
         }
+        ColorType::Rgb | ColorType::Rgba => {
+            if let Some(sort_key) = config.sort_key {
+                create_perceptual_sort_function(sort_key, bytes_per_sample)
+            } else {
+                let channels = config.sort_channel.clone();
+                let mode = config.sort_mode;
+                Box::new(move |pixel| {
+                    channels.iter().fold(0u64, |key, channel| {
+                        let offset = channel.index(color_type) * bytes_per_sample;
+                        let sample = read_sample(pixel, offset, bytes_per_sample) as u64;
+                        match mode {
+                            Some(SortMode::TiedBySum) | None => key + sample,
+                            Some(SortMode::TiedByOrder) => {
+                                (key << (bytes_per_sample * 8)) | sample
+                            }
+                            _ => unreachable!(),
+                        }
+                    })
+                })
+            }
+        }
         _ => unreachable!(),
-    };
-    if config.descending {
-        Box::new(move |pixel| -asc(pixel))
+    }
+}
+
+/// Builds a sort key from a perceptual property (`SortKey`) of the pixel's
+/// resolved RGB color, reading only the first three samples (the alpha
+/// channel of Rgba, if any, does not participate).
+fn create_perceptual_sort_function(sort_key: SortKey, bytes_per_sample: usize) -> SortFn {
+    Box::new(move |pixel| {
+        let r = read_sample(pixel, 0, bytes_per_sample) as f64;
+        let g = read_sample(pixel, bytes_per_sample, bytes_per_sample) as f64;
+        let b = read_sample(pixel, 2 * bytes_per_sample, bytes_per_sample) as f64;
+        match sort_key {
+            SortKey::Luminance => luminance_key(r, g, b),
+            SortKey::Hue => hue_key(r, g, b),
+            SortKey::Saturation => saturation_key(r, g, b),
+        }
+    })
+}
+
+/// Rec. 709 luma, computed in gamma (sample) space.
+fn luminance_key(r: f64, g: f64, b: f64) -> u64 {
+    (0.2126 * r + 0.7152 * g + 0.0722 * b).round() as u64
+}
+
+/// HSV saturation, scaled to a fixed-point integer so `Ord` sorts it correctly.
+fn saturation_key(r: f64, g: f64, b: f64) -> u64 {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    if max == 0.0 {
+        0
     } else {
-        asc
+        (((max - min) / max) * 1_000_000.0).round() as u64
     }
 }
 
+/// HSV hue in degrees, via the standard sextant formula, scaled to a
+/// fixed-point integer and packed with a luminance tie-breaker in the low
+/// bits so achromatic pixels (where hue is undefined) still get a stable,
+/// brightness-ordered position instead of scattering.
+fn hue_key(r: f64, g: f64, b: f64) -> u64 {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue_deg = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    let hue_scaled = (hue_deg * 1000.0).round() as u64;
+    let luminance = luminance_key(r, g, b).clamp(0, 0xFF_FFFF);
+    (hue_scaled << 24) | luminance
+}
+
 fn sort_pixels_tied(
     config: &Config,
     src_buf: &[u8],
@@ -135,22 +388,52 @@ fn sort_pixels_tied(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
     color_type: ColorType,
 ) -> Result<()> {
-    let sort_fn = create_sort_function(config, color_type);
+    let sort_fn = create_sort_function(config, color_type, bytes_per_sample);
+    let threshold = config.threshold();
 
     match config.sort_range {
         SortRange::Row => {
-            sort_by_rows(src_buf, out_buf, width, height, bytes_per_pixel, &sort_fn);
+            sort_by_rows(
+                src_buf,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                &sort_fn,
+                config.descending,
+                threshold,
+                config.invert_threshold,
+            );
         }
         SortRange::Column => {
-            sort_by_columns(src_buf, out_buf, width, height, bytes_per_pixel, &sort_fn);
+            sort_by_columns(
+                src_buf,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                &sort_fn,
+                config.descending,
+                threshold,
+                config.invert_threshold,
+            );
         }
         SortRange::RowMajor => {
-            sort_row_major(src_buf, out_buf, bytes_per_pixel, &sort_fn);
+            sort_row_major(src_buf, out_buf, bytes_per_pixel, &sort_fn, config.descending);
         }
         SortRange::ColumnMajor => {
-            sort_column_major(src_buf, out_buf, width, height, bytes_per_pixel, &sort_fn);
+            sort_column_major(
+                src_buf,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                &sort_fn,
+                config.descending,
+            );
         }
     }
 
@@ -163,14 +446,18 @@ fn sort_by_rows(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> i32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
+    threshold: Option<(u64, u64)>,
+    invert_threshold: bool,
 ) {
     for y in 0..height {
         let start = y * width * bytes_per_pixel;
         let end = start + width * bytes_per_pixel;
         let mut pixels: Vec<&[u8]> = src_buf[start..end].chunks_exact(bytes_per_pixel).collect();
 
-        pixels.sort_by_key(|p| sort_fn(p));
+        sort_line(&mut pixels, sort_fn, descending, threshold, invert_threshold);
+
         let line = &mut out_buf[start..end];
         for (dst, src_pixel) in line.chunks_exact_mut(bytes_per_pixel).zip(pixels.iter()) {
             dst.copy_from_slice(src_pixel);
@@ -184,7 +471,10 @@ fn sort_by_columns(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> i32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
+    threshold: Option<(u64, u64)>,
+    invert_threshold: bool,
 ) {
     for x in 0..width {
         let mut column: Vec<&[u8]> = Vec::with_capacity(height);
@@ -193,7 +483,7 @@ fn sort_by_columns(
             column.push(&src_buf[idx..idx + bytes_per_pixel]);
         }
 
-        column.sort_by_key(|p| sort_fn(p));
+        sort_line(&mut column, sort_fn, descending, threshold, invert_threshold);
 
         for (y, pixel) in column.iter().enumerate() {
             let idx = (y * width + x) * bytes_per_pixel;
@@ -202,14 +492,69 @@ fn sort_by_columns(
     }
 }
 
+/// Sorts one row or column of pixels in place.
+///
+/// With no threshold window, the whole line is sorted. With one, pixels are
+/// walked left-to-right (or top-to-bottom) and a contiguous "interval" opens
+/// while the sort key stays inside `[lo, hi]` — or outside it, when
+/// `invert_threshold` is set — closing as soon as it leaves. Only the pixels
+/// inside each interval are sorted; everything else stays fixed in place.
+/// `sort_fn` is always the ascending key; `descending` reverses each sorted
+/// run afterwards so it composes with the threshold window instead of
+/// requiring the window to be negated to match.
+fn sort_line(
+    pixels: &mut [&[u8]],
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
+    threshold: Option<(u64, u64)>,
+    invert_threshold: bool,
+) {
+    let Some((lo, hi)) = threshold else {
+        pixels.sort_by_key(|p| sort_fn(p));
+        if descending {
+            pixels.reverse();
+        }
+        return;
+    };
+
+    let is_sortable = |pixel: &&[u8]| {
+        let in_band = (lo..=hi).contains(&sort_fn(pixel));
+        in_band != invert_threshold
+    };
+
+    let mut i = 0;
+    while i < pixels.len() {
+        if !is_sortable(&pixels[i]) {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j < pixels.len() && is_sortable(&pixels[j]) {
+            j += 1;
+        }
+
+        pixels[i..j].sort_by_key(|p| sort_fn(p));
+        if descending {
+            pixels[i..j].reverse();
+        }
+
+        i = j + 1;
+    }
+}
+
 fn sort_row_major(
     src_buf: &[u8],
     out_buf: &mut [u8],
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> i32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
 ) {
     let mut pixels: Vec<&[u8]> = src_buf.chunks_exact(bytes_per_pixel).collect();
     pixels.sort_by_key(|p| sort_fn(p));
+    if descending {
+        pixels.reverse();
+    }
 
     for (dst, src_pixel) in out_buf.chunks_exact_mut(bytes_per_pixel).zip(pixels.iter()) {
         dst.copy_from_slice(src_pixel);
@@ -222,10 +567,14 @@ fn sort_column_major(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> i32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
 ) {
     let mut pixels: Vec<&[u8]> = src_buf.chunks_exact(bytes_per_pixel).collect();
     pixels.sort_by_key(|p| sort_fn(p));
+    if descending {
+        pixels.reverse();
+    }
 
     for x in 0..width {
         for y in 0..height {
@@ -243,21 +592,55 @@ fn sort_channels_untied(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
+    color_type: ColorType,
 ) -> Result<()> {
     out_buf.copy_from_slice(src_buf);
 
     match config.sort_range {
         SortRange::Row => {
-            sort_channels_by_rows(config, out_buf, width, height, bytes_per_pixel);
+            sort_channels_by_rows(
+                config,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_sample,
+                color_type,
+            );
         }
         SortRange::Column => {
-            sort_channels_by_columns(config, out_buf, width, height, bytes_per_pixel);
+            sort_channels_by_columns(
+                config,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_sample,
+                color_type,
+            );
         }
         SortRange::RowMajor => {
-            sort_channels_row_major(config, out_buf, width, height, bytes_per_pixel);
+            sort_channels_row_major(
+                config,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_sample,
+                color_type,
+            );
         }
         SortRange::ColumnMajor => {
-            sort_channels_column_major(config, out_buf, width, height, bytes_per_pixel);
+            sort_channels_column_major(
+                config,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_sample,
+                color_type,
+            );
         }
     }
 
@@ -270,29 +653,30 @@ fn sort_channels_by_rows(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
+    color_type: ColorType,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::new();
+    let mut channel_buf: Vec<u16> = Vec::new();
     channel_buf.reserve(width);
 
     for y in 0..height {
         for channel in &config.sort_channel {
             channel_buf.clear();
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
 
-            channel_buf.sort_unstable_by(|a, b| {
-                if config.descending {
-                    b.cmp(a)
-                } else {
-                    a.cmp(b)
-                }
-            });
+            channel_buf.sort_unstable();
+            if config.descending {
+                channel_buf.reverse();
+            }
 
             for (x, &value) in channel_buf.iter().enumerate() {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                out_buf[idx] = value;
+                let idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
+                write_sample(out_buf, idx, value, bytes_per_sample);
             }
         }
     }
@@ -304,29 +688,30 @@ fn sort_channels_by_columns(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
+    color_type: ColorType,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::new();
+    let mut channel_buf: Vec<u16> = Vec::new();
     channel_buf.reserve(height);
 
     for x in 0..width {
         for channel in &config.sort_channel {
             channel_buf.clear();
             for y in 0..height {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
 
-            channel_buf.sort_unstable_by(|a, b| {
-                if config.descending {
-                    b.cmp(a)
-                } else {
-                    a.cmp(b)
-                }
-            });
+            channel_buf.sort_unstable();
+            if config.descending {
+                channel_buf.reverse();
+            }
 
             for (y, &value) in channel_buf.iter().enumerate() {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                out_buf[idx] = value;
+                let idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
+                write_sample(out_buf, idx, value, bytes_per_sample);
             }
         }
     }
@@ -338,31 +723,32 @@ fn sort_channels_row_major(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
+    color_type: ColorType,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::with_capacity(width * height);
+    let mut channel_buf: Vec<u16> = Vec::with_capacity(width * height);
 
     for channel in &config.sort_channel {
         channel_buf.clear();
         for y in 0..height {
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
         }
 
-        channel_buf.sort_unstable_by(|a, b| {
-            if config.descending {
-                b.cmp(a)
-            } else {
-                a.cmp(b)
-            }
-        });
+        channel_buf.sort_unstable();
+        if config.descending {
+            channel_buf.reverse();
+        }
 
         let mut i = 0;
         for y in 0..height {
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                out_buf[idx] = channel_buf[i];
+                let idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
+                write_sample(out_buf, idx, channel_buf[i], bytes_per_sample);
                 i += 1;
             }
         }
@@ -375,25 +761,32 @@ fn sort_channels_column_major(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
+    color_type: ColorType,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::with_capacity(width * height);
+    let mut channel_buf: Vec<u16> = Vec::with_capacity(width * height);
 
     for channel in &args.sort_channel {
         channel_buf.clear();
         for y in 0..height {
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
         }
 
-        channel_buf.sort_unstable_by(|a, b| if args.descending { b.cmp(a) } else { a.cmp(b) });
+        channel_buf.sort_unstable();
+        if args.descending {
+            channel_buf.reverse();
+        }
 
         for x in 0..width {
             for y in 0..height {
-                let dst_idx = (y * width + x) * bytes_per_pixel + channel.index();
+                let dst_idx = (y * width + x) * bytes_per_pixel
+                    + channel.index(color_type) * bytes_per_sample;
                 let src_idx = x * height + y;
-                out_buf[dst_idx] = channel_buf[src_idx];
+                write_sample(out_buf, dst_idx, channel_buf[src_idx], bytes_per_sample);
             }
         }
     }
@@ -1,10 +1,15 @@
+mod formats;
+mod gpu;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
 use anyhow::Result;
 use clap::Parser;
-use png::ColorType;
-use std::fs::File;
-use std::io::BufReader;
+use formats::{Format, ImageBuffer};
+use png::{BitDepth, ColorType};
 
-type SortFn = Box<dyn Fn(&&[u8]) -> u32>;
+type SortFn = Box<dyn Fn(&&[u8]) -> u64>;
 
 #[derive(clap::ValueEnum, Clone, Copy, Debug)]
 enum SortRange {
@@ -26,6 +31,7 @@ enum ColorChannel {
     R,
     G,
     B,
+    A,
 }
 
 impl ColorChannel {
@@ -34,10 +40,20 @@ impl ColorChannel {
             ColorChannel::R => 0,
             ColorChannel::G => 1,
             ColorChannel::B => 2,
+            ColorChannel::A => 3,
         }
     }
 }
 
+/// How to sort an indexed (palette) image.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum IndexedMode {
+    /// Move index bytes around, keyed by their palette entry's color.
+    Rearrange,
+    /// Leave index bytes in place and sort the palette entries instead.
+    Recolor,
+}
+
 #[derive(Parser, Debug)]
 struct Args {
     #[clap(short, long)]
@@ -56,10 +72,48 @@ struct Args {
     /// For Untied: each channel is sorted independently.
     #[clap(long, value_delimiter = ',', default_value = "r,g,b")]
     sort_channel: Vec<ColorChannel>,
+    /// How to sort an indexed (palette) PNG.
+    #[clap(long, default_value = "rearrange")]
+    indexed_mode: IndexedMode,
+    /// Lower bound (inclusive) of the sort-key window for threshold/interval sorting.
+    /// Only pixels whose key falls in [threshold_lower, threshold_upper] are sorted;
+    /// the rest stay fixed as boundaries. Only applies to Row/Column sort ranges.
+    #[clap(long)]
+    threshold_lower: Option<u64>,
+    /// Upper bound (inclusive) of the sort-key window. See `threshold_lower`.
+    #[clap(long)]
+    threshold_upper: Option<u64>,
+    /// Input/output container format. Inferred from file extension when omitted.
+    #[clap(long)]
+    format: Option<Format>,
+    /// Treat fully-transparent pixels as fixed boundaries instead of sorting them.
+    #[clap(long)]
+    ignore_transparent: bool,
+    /// Weight each color sample by its pixel's alpha before computing the sort
+    /// key, so translucent pixels sort toward one end.
+    #[clap(long)]
+    premultiply_alpha: bool,
+    /// Stream the PNG row by row instead of buffering the whole image, so
+    /// peak memory is O(width) rather than O(width*height). Only supports
+    /// PNG-to-PNG, the Row sort range, and tied sort modes.
+    #[clap(long)]
+    streaming: bool,
+    /// For the Column sort range, bound peak input memory to roughly this
+    /// many bytes by re-decoding the PNG once per band of columns instead of
+    /// buffering the whole frame. Only supports PNG-to-PNG.
+    #[clap(long)]
+    max_memory: Option<u64>,
+    /// Sort each row/column in parallel on the GPU (via wgpu) instead of the
+    /// CPU. Falls back to the CPU path whenever no adapter is available, a
+    /// line is too long for one workgroup, or a threshold/transparency mask
+    /// is set (those split a line into variable-length runs the GPU path
+    /// doesn't support). Only applies to the Row and Column sort ranges.
+    #[clap(long)]
+    gpu: bool,
 }
 
 impl Args {
-    fn validate(&self, color_type: ColorType) -> Result<()> {
+    fn validate(&self, color_type: ColorType, bit_depth: BitDepth) -> Result<()> {
         let mut sorted_channels = self.sort_channel.clone();
         sorted_channels.sort();
         sorted_channels.dedup();
@@ -68,7 +122,17 @@ impl Args {
         }
 
         match color_type {
-            ColorType::Rgb | ColorType::Rgba => {
+            ColorType::Rgb => {
+                if self.sort_channel.contains(&ColorChannel::A) {
+                    anyhow::bail!("Rgb images have no alpha channel; remove A from sort_channel");
+                }
+                if let Some(SortMode::Untied) = self.sort_mode
+                    && self.sort_channel.is_empty()
+                {
+                    anyhow::bail!("Sort channel should be specified when using Untied sort mode");
+                }
+            }
+            ColorType::Rgba => {
                 if let Some(SortMode::Untied) = self.sort_mode
                     && self.sort_channel.is_empty()
                 {
@@ -83,58 +147,498 @@ impl Args {
                     anyhow::bail!("Channel option is not applicable for Grayscale images");
                 }
             }
-            ColorType::Indexed => anyhow::bail!("Indexed color type is not supported"),
+            ColorType::Indexed => {
+                if self.sort_channel.contains(&ColorChannel::A) {
+                    anyhow::bail!(
+                        "Palette colors have no alpha channel; remove A from sort_channel"
+                    );
+                }
+                if let Some(SortMode::Untied) = self.sort_mode {
+                    anyhow::bail!("Untied sort mode is not applicable for Indexed images");
+                }
+            }
+        }
+
+        let has_alpha = matches!(color_type, ColorType::Rgba | ColorType::GrayscaleAlpha);
+        if (self.ignore_transparent || self.premultiply_alpha) && !has_alpha {
+            anyhow::bail!(
+                "--ignore-transparent and --premultiply-alpha require an image with an alpha channel"
+            );
+        }
+        if (self.ignore_transparent || self.premultiply_alpha)
+            && self.sort_mode == Some(SortMode::Untied)
+        {
+            anyhow::bail!(
+                "--ignore-transparent and --premultiply-alpha do not support Untied sort mode"
+            );
+        }
+
+        match bit_depth {
+            BitDepth::One | BitDepth::Two | BitDepth::Four => {
+                anyhow::bail!(
+                    "Bit depths below 8 ({:?}) are not supported; expand the image to 8-bit first",
+                    bit_depth
+                );
+            }
+            BitDepth::Eight | BitDepth::Sixteen => {}
+        }
+
+        if self.threshold_lower.is_some() != self.threshold_upper.is_some() {
+            anyhow::bail!(
+                "Both --threshold-lower and --threshold-upper must be provided together"
+            );
+        }
+        if let (Some(lo), Some(hi)) = (self.threshold_lower, self.threshold_upper) {
+            if lo > hi {
+                anyhow::bail!("--threshold-lower must not exceed --threshold-upper");
+            }
+            if matches!(self.sort_range, SortRange::RowMajor | SortRange::ColumnMajor) {
+                anyhow::bail!(
+                    "Threshold sorting is only supported for Row and Column sort ranges"
+                );
+            }
+            if self.sort_mode == Some(SortMode::Untied) {
+                anyhow::bail!("--threshold-lower/--threshold-upper do not support Untied sort mode");
+            }
+        }
+
+        if self.streaming {
+            if !matches!(self.sort_range, SortRange::Row) {
+                anyhow::bail!("--streaming only supports the Row sort range");
+            }
+            if self.sort_mode == Some(SortMode::Untied) {
+                anyhow::bail!("--streaming does not support Untied sort mode");
+            }
+            if color_type == ColorType::Indexed {
+                anyhow::bail!("--streaming does not support Indexed images");
+            }
+        }
+
+        if self.max_memory.is_some() {
+            if !matches!(self.sort_range, SortRange::Column) {
+                anyhow::bail!("--max-memory only applies to the Column sort range");
+            }
+            if color_type == ColorType::Indexed {
+                anyhow::bail!("--max-memory does not support Indexed images");
+            }
+            if self.sort_mode == Some(SortMode::Untied) {
+                anyhow::bail!("--max-memory does not support Untied sort mode");
+            }
+        }
+
+        if self.gpu {
+            if !matches!(self.sort_range, SortRange::Row | SortRange::Column) {
+                anyhow::bail!("--gpu only applies to the Row and Column sort ranges");
+            }
+            if self.streaming || self.max_memory.is_some() {
+                anyhow::bail!("--gpu cannot be combined with --streaming or --max-memory");
+            }
+            if self.sort_mode == Some(SortMode::Untied) {
+                anyhow::bail!("--gpu does not support Untied sort mode");
+            }
         }
 
         Ok(())
     }
+
+    fn threshold(&self) -> Option<(u64, u64)> {
+        self.threshold_lower.zip(self.threshold_upper)
+    }
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+
+    let input_format = match args.format {
+        Some(format) => format,
+        None => Format::from_path(&args.input)?,
+    };
+    let output_format = match args.format {
+        Some(format) => format,
+        None => Format::from_path(&args.output)?,
+    };
+
+    if args.streaming || args.max_memory.is_some() {
+        anyhow::ensure!(
+            input_format == Format::Png && output_format == Format::Png,
+            "--streaming and --max-memory require both input and output to be PNG"
+        );
+
+        let (color_type, bit_depth) = peek_png_header(&args.input)?;
+        args.validate(color_type, bit_depth)?;
+
+        if args.streaming {
+            return run_streaming_row_sort(&args, color_type, bit_depth);
+        }
+        if let Some(max_memory) = args.max_memory {
+            return run_chunked_column_sort(&args, color_type, bit_depth, max_memory as usize);
+        }
+    }
+
+    let image = input_format.decode(&args.input)?;
+    args.validate(image.color_type, image.bit_depth)?;
+
+    let (buffer, palette, trns) = if image.color_type == ColorType::Indexed {
+        let palette = image
+            .palette
+            .as_deref()
+            .expect("Indexed PNG is missing its PLTE chunk");
+        let (sorted_buf, output_palette, output_trns) = process_indexed_image(
+            &args,
+            &image.buffer,
+            image.width,
+            image.height,
+            palette,
+            image.trns.as_deref(),
+        )?;
+        (sorted_buf, Some(output_palette), output_trns)
+    } else {
+        let sorted_buf = process_image(
+            &args,
+            &image.buffer,
+            image.width,
+            image.height,
+            image.color_type,
+            image.bit_depth,
+        )?;
+        (sorted_buf, None, image.trns)
+    };
+
+    if palette.is_some() && output_format != Format::Png {
+        anyhow::bail!("Indexed images can only be written to the PNG format");
+    }
+
+    let output_image = ImageBuffer {
+        width: image.width,
+        height: image.height,
+        color_type: image.color_type,
+        bit_depth: image.bit_depth,
+        buffer,
+        palette,
+        trns,
+    };
+    output_format.encode(&args.output, &output_image)?;
+
+    Ok(())
+}
+
+/// Reads just the PNG header (no pixel data) to learn the color type and bit
+/// depth up front, so the streaming/chunked paths can validate and size
+/// their buffers before committing to a full decode.
+fn peek_png_header(path: &str) -> Result<(ColorType, BitDepth)> {
+    let file = File::open(path)?;
+    let decoder = png::Decoder::new(BufReader::new(file));
+    let reader = decoder.read_info()?;
+    let info = reader.info();
+    Ok((info.color_type, info.bit_depth))
+}
+
+fn channels_for(color_type: ColorType) -> usize {
+    match color_type {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        ColorType::Indexed => 1,
+    }
+}
+
+/// Sorts a PNG row by row, decoding and writing one scanline at a time so
+/// peak memory stays O(width) instead of O(width*height). Only the Row sort
+/// range with a tied sort mode is supported; threshold windows and
+/// transparency masking still work since they operate within a single row.
+fn run_streaming_row_sort(args: &Args, color_type: ColorType, bit_depth: BitDepth) -> Result<()> {
+    let channels = channels_for(color_type);
+    let bytes_per_sample = if matches!(bit_depth, BitDepth::Sixteen) {
+        2
+    } else {
+        1
+    };
+    let bytes_per_pixel = channels * bytes_per_sample;
+
+    let sort_fn = create_sort_function(args, color_type, bytes_per_sample);
+    let threshold = args.threshold();
+    let transparent_offset = if args.ignore_transparent {
+        alpha_offset(color_type, bytes_per_sample)
+    } else {
+        None
+    };
+
     let input_file = File::open(&args.input)?;
-    let output_file = File::create(&args.output)?;
-    let reader = BufReader::new(input_file);
-    let decoder = png::Decoder::new(reader);
+    let decoder = png::Decoder::new(BufReader::new(input_file));
     let mut reader = decoder.read_info()?;
-    let info = reader.info();
+    let width = reader.info().width;
+    let height = reader.info().height;
+
+    let output_file = File::create(&args.output)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(output_file), width, height);
+    encoder.set_color(color_type);
+    encoder.set_depth(bit_depth);
+    let writer = encoder.write_header()?;
+    let mut stream_writer = writer.stream_writer()?;
+
+    let mut row_buf = vec![0u8; width as usize * bytes_per_pixel];
+    while let Some(row) = reader.next_row()? {
+        row_buf.copy_from_slice(row.data());
+        let mut pixels: Vec<&[u8]> = row_buf.chunks_exact(bytes_per_pixel).collect();
+        sort_line(
+            &mut pixels,
+            &sort_fn,
+            args.descending,
+            threshold,
+            transparent_offset,
+        );
+        for pixel in &pixels {
+            stream_writer.write_all(pixel)?;
+        }
+    }
+    stream_writer.finish()?;
+
+    Ok(())
+}
 
-    let color_type = info.color_type;
-    let bit_depth = info.bit_depth;
+/// Sorts a PNG by columns without ever holding the whole decoded frame in
+/// memory: the image is re-decoded once per band of columns, so only
+/// `band_width * height` pixels of input are resident at a time, with the
+/// band width sized to fit `max_memory` bytes. The sorted output still has
+/// to be assembled in full before it can be written, since a column sort
+/// touches every row.
+fn run_chunked_column_sort(
+    args: &Args,
+    color_type: ColorType,
+    bit_depth: BitDepth,
+    max_memory: usize,
+) -> Result<()> {
+    let channels = channels_for(color_type);
+    let bytes_per_sample = if matches!(bit_depth, BitDepth::Sixteen) {
+        2
+    } else {
+        1
+    };
+    let bytes_per_pixel = channels * bytes_per_sample;
+
+    let header_file = File::open(&args.input)?;
+    let header_decoder = png::Decoder::new(BufReader::new(header_file));
+    let header_reader = header_decoder.read_info()?;
+    let width = header_reader.info().width as usize;
+    let height = header_reader.info().height as usize;
+
+    let sort_fn = create_sort_function(args, color_type, bytes_per_sample);
+    let threshold = args.threshold();
+    let transparent_offset = if args.ignore_transparent {
+        alpha_offset(color_type, bytes_per_sample)
+    } else {
+        None
+    };
+
+    let bytes_per_column = height * bytes_per_pixel;
+    let band_width = (max_memory / bytes_per_column.max(1)).clamp(1, width);
+
+    let mut out_buf = vec![0u8; width * height * bytes_per_pixel];
+    let mut row_buf = vec![0u8; width * bytes_per_pixel];
+
+    let mut col_start = 0;
+    while col_start < width {
+        let col_end = (col_start + band_width).min(width);
+        let band_cols = col_end - col_start;
+
+        let band_file = File::open(&args.input)?;
+        let band_decoder = png::Decoder::new(BufReader::new(band_file));
+        let mut band_reader = band_decoder.read_info()?;
+
+        let mut band_buf = vec![0u8; band_cols * height * bytes_per_pixel];
+        let mut y = 0;
+        while let Some(row) = band_reader.next_row()? {
+            row_buf.copy_from_slice(row.data());
+            let band_row_start = y * band_cols * bytes_per_pixel;
+            band_buf[band_row_start..band_row_start + band_cols * bytes_per_pixel].copy_from_slice(
+                &row_buf[col_start * bytes_per_pixel..col_end * bytes_per_pixel],
+            );
+            y += 1;
+        }
+
+        for x in 0..band_cols {
+            let mut column: Vec<&[u8]> = Vec::with_capacity(height);
+            for y in 0..height {
+                let idx = (y * band_cols + x) * bytes_per_pixel;
+                column.push(&band_buf[idx..idx + bytes_per_pixel]);
+            }
 
-    args.validate(color_type)?;
+            sort_line(
+                &mut column,
+                &sort_fn,
+                args.descending,
+                threshold,
+                transparent_offset,
+            );
 
-    let width = info.width;
-    let height = info.height;
-    let mut src_buf = vec![0; reader.output_buffer_size().unwrap()];
-    reader.next_frame(&mut src_buf)?;
+            for (y, pixel) in column.iter().enumerate() {
+                let out_idx = (y * width + (col_start + x)) * bytes_per_pixel;
+                out_buf[out_idx..out_idx + bytes_per_pixel].copy_from_slice(pixel);
+            }
+        }
 
-    let sorted_buf = process_image(&args, &src_buf, width as usize, height as usize, color_type)?;
+        col_start = col_end;
+    }
 
-    let mut encoder = png::Encoder::new(output_file, width, height);
+    let output_file = File::create(&args.output)?;
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(output_file), width as u32, height as u32);
     encoder.set_color(color_type);
     encoder.set_depth(bit_depth);
     let mut writer = encoder.write_header()?;
-    writer.write_image_data(&sorted_buf)?;
+    writer.write_image_data(&out_buf)?;
     writer.finish()?;
 
     Ok(())
 }
 
+/// Sorts an indexed (palette) image, either by rearranging index bytes keyed by
+/// their resolved palette color, or by re-sorting the palette itself in place.
+/// Returns the (possibly reordered) pixel buffer, the palette to encode it
+/// with, and the tRNS table reordered to match that palette.
+fn process_indexed_image(
+    args: &Args,
+    src_buf: &[u8],
+    width: usize,
+    height: usize,
+    palette: &[u8],
+    trns: Option<&[u8]>,
+) -> Result<(Vec<u8>, Vec<u8>, Option<Vec<u8>>)> {
+    match args.indexed_mode {
+        IndexedMode::Rearrange => {
+            let mut out_buf = vec![0; src_buf.len()];
+            let sort_fn = create_indexed_sort_function(args, palette);
+            let threshold = args.threshold();
+
+            match args.sort_range {
+                SortRange::Row => {
+                    sort_by_rows(
+                        src_buf,
+                        &mut out_buf,
+                        width,
+                        height,
+                        1,
+                        &sort_fn,
+                        args.descending,
+                        threshold,
+                        None,
+                        None,
+                    );
+                }
+                SortRange::Column => {
+                    sort_by_columns(
+                        src_buf,
+                        &mut out_buf,
+                        width,
+                        height,
+                        1,
+                        &sort_fn,
+                        args.descending,
+                        threshold,
+                        None,
+                        None,
+                    );
+                }
+                SortRange::RowMajor => {
+                    sort_row_major(src_buf, &mut out_buf, 1, &sort_fn, args.descending);
+                }
+                SortRange::ColumnMajor => {
+                    sort_column_major(
+                        src_buf,
+                        &mut out_buf,
+                        width,
+                        height,
+                        1,
+                        &sort_fn,
+                        args.descending,
+                    );
+                }
+            }
+
+            Ok((out_buf, palette.to_vec(), trns.map(|t| t.to_vec())))
+        }
+        IndexedMode::Recolor => {
+            let rgb_sort_fn = create_sort_function(args, ColorType::Rgb, 1);
+            let entry_count = palette.len() / 3;
+
+            let mut order: Vec<usize> = (0..entry_count).collect();
+            order.sort_by_key(|&i| {
+                let entry = &palette[i * 3..i * 3 + 3];
+                rgb_sort_fn(&entry)
+            });
+            if args.descending {
+                order.reverse();
+            }
+
+            let mut new_palette = vec![0u8; palette.len()];
+            for (rank, &original) in order.iter().enumerate() {
+                new_palette[rank * 3..rank * 3 + 3]
+                    .copy_from_slice(&palette[original * 3..original * 3 + 3]);
+            }
+
+            let new_trns = trns.map(|trns| {
+                let mut new_trns = vec![255u8; entry_count];
+                for (rank, &original) in order.iter().enumerate() {
+                    if let Some(&alpha) = trns.get(original) {
+                        new_trns[rank] = alpha;
+                    }
+                }
+                new_trns
+            });
+
+            Ok((src_buf.to_vec(), new_palette, new_trns))
+        }
+    }
+}
+
+/// Builds a sort key for an index byte by resolving it through the palette and
+/// reusing the RGB sort function on the resulting color.
+fn create_indexed_sort_function(args: &Args, palette: &[u8]) -> SortFn {
+    let rgb_sort_fn = create_sort_function(args, ColorType::Rgb, 1);
+    let palette = palette.to_vec();
+    Box::new(move |pixel| {
+        let offset = pixel[0] as usize * 3;
+        let entry = &palette[offset..offset + 3];
+        rgb_sort_fn(&entry)
+    })
+}
+
+/// Reads one channel sample (1 or 2 bytes, big-endian) out of a pixel slice.
+fn read_sample(buf: &[u8], offset: usize, bytes_per_sample: usize) -> u16 {
+    if bytes_per_sample == 2 {
+        u16::from_be_bytes([buf[offset], buf[offset + 1]])
+    } else {
+        buf[offset] as u16
+    }
+}
+
+/// Writes one channel sample back as 1 or 2 big-endian bytes.
+fn write_sample(buf: &mut [u8], offset: usize, value: u16, bytes_per_sample: usize) {
+    if bytes_per_sample == 2 {
+        let bytes = value.to_be_bytes();
+        buf[offset] = bytes[0];
+        buf[offset + 1] = bytes[1];
+    } else {
+        buf[offset] = value as u8;
+    }
+}
+
 fn process_image(
     args: &Args,
     src_buf: &[u8],
     width: usize,
     height: usize,
     color_type: ColorType,
+    bit_depth: BitDepth,
 ) -> Result<Vec<u8>> {
-    let bytes_per_pixel = match color_type {
-        ColorType::Grayscale => 1,
-        ColorType::GrayscaleAlpha => 2,
-        ColorType::Rgb => 3,
-        ColorType::Rgba => 4,
-        ColorType::Indexed => unreachable!(),
+    let channels = channels_for(color_type);
+    let bytes_per_sample = if matches!(bit_depth, BitDepth::Sixteen) {
+        2
+    } else {
+        1
     };
+    let bytes_per_pixel = channels * bytes_per_sample;
 
     let mut out_buf = vec![0; src_buf.len()];
 
@@ -146,31 +650,67 @@ fn process_image(
             width,
             height,
             bytes_per_pixel,
+            bytes_per_sample,
             color_type,
         )?;
     } else {
-        sort_channels_untied(args, src_buf, &mut out_buf, width, height, bytes_per_pixel)?;
+        sort_channels_untied(
+            args,
+            src_buf,
+            &mut out_buf,
+            width,
+            height,
+            bytes_per_pixel,
+            bytes_per_sample,
+        )?;
     }
 
     Ok(out_buf)
 }
 
-fn create_sort_function(args: &Args, color_type: ColorType) -> SortFn {
+fn create_sort_function(args: &Args, color_type: ColorType, bytes_per_sample: usize) -> SortFn {
+    let max_sample = if bytes_per_sample == 2 {
+        u16::MAX as u64
+    } else {
+        u8::MAX as u64
+    };
+
     match color_type {
-        ColorType::Grayscale | ColorType::GrayscaleAlpha => Box::new(|pixel| pixel[0] as u32),
+        ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+            let premultiply = args.premultiply_alpha && color_type == ColorType::GrayscaleAlpha;
+            Box::new(move |pixel| {
+                let gray = read_sample(pixel, 0, bytes_per_sample) as u64;
+                if premultiply {
+                    let alpha = read_sample(pixel, bytes_per_sample, bytes_per_sample) as u64;
+                    gray * alpha / max_sample
+                } else {
+                    gray
+                }
+            })
+        }
         ColorType::Rgb | ColorType::Rgba => {
             let channels = args.sort_channel.clone();
             let mode = args.sort_mode;
+            let premultiply = args.premultiply_alpha && color_type == ColorType::Rgba;
             Box::new(move |pixel| {
-                let mut key = 0u32;
+                let alpha = if premultiply {
+                    read_sample(pixel, 3 * bytes_per_sample, bytes_per_sample) as u64
+                } else {
+                    max_sample
+                };
+                let mut key = 0u64;
                 for channel in &channels {
-                    let idx = channel.index();
+                    let offset = channel.index() * bytes_per_sample;
+                    let mut sample = read_sample(pixel, offset, bytes_per_sample) as u64;
+                    if premultiply && *channel != ColorChannel::A {
+                        sample = sample * alpha / max_sample;
+                    }
                     match mode {
                         Some(SortMode::TiedBySum) | None => {
-                            key += pixel[idx] as u32;
+                            key += sample;
                         }
                         Some(SortMode::TiedByOrder) => {
-                            key = (key << 8) | (pixel[idx] as u32);
+                            key = (key << (bytes_per_sample * 8)) | sample;
                         }
                         _ => unreachable!(),
                     }
@@ -189,9 +729,21 @@ fn sort_pixels_tied(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
     color_type: ColorType,
 ) -> Result<()> {
-    let sort_fn = create_sort_function(args, color_type);
+    let sort_fn = create_sort_function(args, color_type, bytes_per_sample);
+    let threshold = args.threshold();
+    let transparent_offset = if args.ignore_transparent {
+        alpha_offset(color_type, bytes_per_sample)
+    } else {
+        None
+    };
+    let gpu_sorter = if args.gpu && threshold.is_none() && transparent_offset.is_none() {
+        gpu::GpuSorter::new()
+    } else {
+        None
+    };
 
     match args.sort_range {
         SortRange::Row => {
@@ -203,6 +755,9 @@ fn sort_pixels_tied(
                 bytes_per_pixel,
                 &sort_fn,
                 args.descending,
+                threshold,
+                transparent_offset,
+                gpu_sorter.as_ref(),
             );
         }
         SortRange::Column => {
@@ -214,6 +769,9 @@ fn sort_pixels_tied(
                 bytes_per_pixel,
                 &sort_fn,
                 args.descending,
+                threshold,
+                transparent_offset,
+                gpu_sorter.as_ref(),
             );
         }
         SortRange::RowMajor => {
@@ -235,24 +793,51 @@ fn sort_pixels_tied(
     Ok(())
 }
 
+/// Byte offset of the alpha sample within a pixel (and its width in bytes),
+/// if `color_type` has one.
+fn alpha_offset(color_type: ColorType, bytes_per_sample: usize) -> Option<(usize, usize)> {
+    match color_type {
+        ColorType::Rgba => Some((3 * bytes_per_sample, bytes_per_sample)),
+        ColorType::GrayscaleAlpha => Some((bytes_per_sample, bytes_per_sample)),
+        ColorType::Grayscale | ColorType::Rgb | ColorType::Indexed => None,
+    }
+}
+
 fn sort_by_rows(
     src_buf: &[u8],
     out_buf: &mut [u8],
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> u32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
     descending: bool,
+    threshold: Option<(u64, u64)>,
+    transparent_offset: Option<(usize, usize)>,
+    gpu: Option<&gpu::GpuSorter>,
 ) {
+    if threshold.is_none()
+        && transparent_offset.is_none()
+        && let Some(sorter) = gpu
+        && gpu_sort_rows(
+            sorter,
+            src_buf,
+            out_buf,
+            width,
+            height,
+            bytes_per_pixel,
+            sort_fn,
+            descending,
+        )
+    {
+        return;
+    }
+
     for y in 0..height {
         let start = y * width * bytes_per_pixel;
         let end = start + width * bytes_per_pixel;
         let mut pixels: Vec<&[u8]> = src_buf[start..end].chunks_exact(bytes_per_pixel).collect();
 
-        pixels.sort_by_key(|p| sort_fn(p));
-        if descending {
-            pixels.reverse();
-        }
+        sort_line(&mut pixels, sort_fn, descending, threshold, transparent_offset);
 
         let line = &mut out_buf[start..end];
         for (dst, src_pixel) in line.chunks_exact_mut(bytes_per_pixel).zip(pixels.iter()) {
@@ -267,9 +852,29 @@ fn sort_by_columns(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> u32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
     descending: bool,
+    threshold: Option<(u64, u64)>,
+    transparent_offset: Option<(usize, usize)>,
+    gpu: Option<&gpu::GpuSorter>,
 ) {
+    if threshold.is_none()
+        && transparent_offset.is_none()
+        && let Some(sorter) = gpu
+        && gpu_sort_columns(
+            sorter,
+            src_buf,
+            out_buf,
+            width,
+            height,
+            bytes_per_pixel,
+            sort_fn,
+            descending,
+        )
+    {
+        return;
+    }
+
     for x in 0..width {
         let mut column: Vec<&[u8]> = Vec::with_capacity(height);
         for y in 0..height {
@@ -277,10 +882,7 @@ fn sort_by_columns(
             column.push(&src_buf[idx..idx + bytes_per_pixel]);
         }
 
-        column.sort_by_key(|p| sort_fn(p));
-        if descending {
-            column.reverse();
-        }
+        sort_line(&mut column, sort_fn, descending, threshold, transparent_offset);
 
         for (y, pixel) in column.iter().enumerate() {
             let idx = (y * width + x) * bytes_per_pixel;
@@ -289,11 +891,144 @@ fn sort_by_columns(
     }
 }
 
+/// Sorts every row of `src_buf` in parallel on the GPU, gathering pixels back
+/// into `out_buf` according to the bitonic network's output permutation.
+/// Returns `false` (leaving `out_buf` untouched) whenever the GPU backend
+/// can't handle the line length, so the caller falls back to the CPU path.
+fn gpu_sort_rows(
+    gpu: &gpu::GpuSorter,
+    src_buf: &[u8],
+    out_buf: &mut [u8],
+    width: usize,
+    height: usize,
+    bytes_per_pixel: usize,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
+) -> bool {
+    let keys: Vec<u64> = src_buf
+        .chunks_exact(bytes_per_pixel)
+        .map(|pixel| sort_fn(&pixel))
+        .collect();
+    let Some(mut permutation) = gpu.sort_lines(&keys, height, width) else {
+        return false;
+    };
+    if descending {
+        for row in permutation.chunks_mut(width) {
+            row.reverse();
+        }
+    }
+
+    for (y, row) in permutation.chunks(width).enumerate() {
+        for (x, &src_x) in row.iter().enumerate() {
+            let dst = (y * width + x) * bytes_per_pixel;
+            let src = (y * width + src_x as usize) * bytes_per_pixel;
+            out_buf[dst..dst + bytes_per_pixel]
+                .copy_from_slice(&src_buf[src..src + bytes_per_pixel]);
+        }
+    }
+    true
+}
+
+/// Column-sort counterpart of [`gpu_sort_rows`]: sorts every column in
+/// parallel on the GPU and gathers pixels back into `out_buf`.
+fn gpu_sort_columns(
+    gpu: &gpu::GpuSorter,
+    src_buf: &[u8],
+    out_buf: &mut [u8],
+    width: usize,
+    height: usize,
+    bytes_per_pixel: usize,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
+) -> bool {
+    let mut keys = Vec::with_capacity(width * height);
+    for x in 0..width {
+        for y in 0..height {
+            let idx = (y * width + x) * bytes_per_pixel;
+            keys.push(sort_fn(&&src_buf[idx..idx + bytes_per_pixel]));
+        }
+    }
+    let Some(mut permutation) = gpu.sort_lines(&keys, width, height) else {
+        return false;
+    };
+    if descending {
+        for column in permutation.chunks_mut(height) {
+            column.reverse();
+        }
+    }
+
+    for (x, column) in permutation.chunks(height).enumerate() {
+        for (y, &src_y) in column.iter().enumerate() {
+            let dst = (y * width + x) * bytes_per_pixel;
+            let src = (src_y as usize * width + x) * bytes_per_pixel;
+            out_buf[dst..dst + bytes_per_pixel]
+                .copy_from_slice(&src_buf[src..src + bytes_per_pixel]);
+        }
+    }
+    true
+}
+
+/// Sorts one row or column of pixels in place.
+///
+/// With neither constraint, the whole line is sorted. With a threshold
+/// window, only contiguous runs of pixels whose key falls inside `[lo, hi]`
+/// are sorted. With `transparent_offset` set, pixels whose alpha sample is
+/// zero are also treated as fixed boundaries. Either constraint splits the
+/// line into runs that are sorted independently, with boundary pixels left
+/// unchanged in place.
+fn sort_line(
+    pixels: &mut [&[u8]],
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
+    descending: bool,
+    threshold: Option<(u64, u64)>,
+    transparent_offset: Option<(usize, usize)>,
+) {
+    if threshold.is_none() && transparent_offset.is_none() {
+        pixels.sort_by_key(|p| sort_fn(p));
+        if descending {
+            pixels.reverse();
+        }
+        return;
+    }
+
+    let is_sortable = |pixel: &&[u8]| {
+        if let Some((offset, bytes_per_sample)) = transparent_offset
+            && read_sample(pixel, offset, bytes_per_sample) == 0
+        {
+            return false;
+        }
+        match threshold {
+            Some((lo, hi)) => (lo..=hi).contains(&sort_fn(pixel)),
+            None => true,
+        }
+    };
+
+    let mut i = 0;
+    while i < pixels.len() {
+        if !is_sortable(&pixels[i]) {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j < pixels.len() && is_sortable(&pixels[j]) {
+            j += 1;
+        }
+
+        pixels[i..j].sort_by_key(|p| sort_fn(p));
+        if descending {
+            pixels[i..j].reverse();
+        }
+
+        i = j + 1;
+    }
+}
+
 fn sort_row_major(
     src_buf: &[u8],
     out_buf: &mut [u8],
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> u32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
     descending: bool,
 ) {
     let mut pixels: Vec<&[u8]> = src_buf.chunks_exact(bytes_per_pixel).collect();
@@ -313,7 +1048,7 @@ fn sort_column_major(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
-    sort_fn: &dyn Fn(&&[u8]) -> u32,
+    sort_fn: &dyn Fn(&&[u8]) -> u64,
     descending: bool,
 ) {
     let mut pixels: Vec<&[u8]> = src_buf.chunks_exact(bytes_per_pixel).collect();
@@ -338,21 +1073,43 @@ fn sort_channels_untied(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
 ) -> Result<()> {
     out_buf.copy_from_slice(src_buf);
 
     match args.sort_range {
         SortRange::Row => {
-            sort_channels_by_rows(args, out_buf, width, height, bytes_per_pixel);
+            sort_channels_by_rows(args, out_buf, width, height, bytes_per_pixel, bytes_per_sample);
         }
         SortRange::Column => {
-            sort_channels_by_columns(args, out_buf, width, height, bytes_per_pixel);
+            sort_channels_by_columns(
+                args,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_sample,
+            );
         }
         SortRange::RowMajor => {
-            sort_channels_row_major(args, out_buf, width, height, bytes_per_pixel);
+            sort_channels_row_major(
+                args,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_sample,
+            );
         }
         SortRange::ColumnMajor => {
-            sort_channels_column_major(args, out_buf, width, height, bytes_per_pixel);
+            sort_channels_column_major(
+                args,
+                out_buf,
+                width,
+                height,
+                bytes_per_pixel,
+                bytes_per_sample,
+            );
         }
     }
 
@@ -365,16 +1122,18 @@ fn sort_channels_by_rows(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::new();
+    let mut channel_buf: Vec<u16> = Vec::new();
     channel_buf.reserve(width);
 
     for y in 0..height {
         for channel in &args.sort_channel {
             channel_buf.clear();
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
 
             channel_buf.sort_unstable();
@@ -383,8 +1142,9 @@ fn sort_channels_by_rows(
             }
 
             for (x, &value) in channel_buf.iter().enumerate() {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                out_buf[idx] = value;
+                let idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
+                write_sample(out_buf, idx, value, bytes_per_sample);
             }
         }
     }
@@ -396,16 +1156,18 @@ fn sort_channels_by_columns(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::new();
+    let mut channel_buf: Vec<u16> = Vec::new();
     channel_buf.reserve(height);
 
     for x in 0..width {
         for channel in &args.sort_channel {
             channel_buf.clear();
             for y in 0..height {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
 
             channel_buf.sort_unstable();
@@ -414,8 +1176,9 @@ fn sort_channels_by_columns(
             }
 
             for (y, &value) in channel_buf.iter().enumerate() {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                out_buf[idx] = value;
+                let idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
+                write_sample(out_buf, idx, value, bytes_per_sample);
             }
         }
     }
@@ -427,15 +1190,17 @@ fn sort_channels_row_major(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::with_capacity(width * height);
+    let mut channel_buf: Vec<u16> = Vec::with_capacity(width * height);
 
     for channel in &args.sort_channel {
         channel_buf.clear();
         for y in 0..height {
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
         }
 
@@ -447,8 +1212,9 @@ fn sort_channels_row_major(
         let mut i = 0;
         for y in 0..height {
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                out_buf[idx] = channel_buf[i];
+                let idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
+                write_sample(out_buf, idx, channel_buf[i], bytes_per_sample);
                 i += 1;
             }
         }
@@ -461,15 +1227,17 @@ fn sort_channels_column_major(
     width: usize,
     height: usize,
     bytes_per_pixel: usize,
+    bytes_per_sample: usize,
 ) {
-    let mut channel_buf: Vec<u8> = Vec::with_capacity(width * height);
+    let mut channel_buf: Vec<u16> = Vec::with_capacity(width * height);
 
     for channel in &args.sort_channel {
         channel_buf.clear();
         for y in 0..height {
             for x in 0..width {
-                let idx = (y * width + x) * bytes_per_pixel + channel.index();
-                channel_buf.push(out_buf[idx]);
+                let idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
+                channel_buf.push(read_sample(out_buf, idx, bytes_per_sample));
             }
         }
 
@@ -480,9 +1248,10 @@ fn sort_channels_column_major(
 
         for x in 0..width {
             for y in 0..height {
-                let dst_idx = (y * width + x) * bytes_per_pixel + channel.index();
+                let dst_idx =
+                    (y * width + x) * bytes_per_pixel + channel.index() * bytes_per_sample;
                 let src_idx = x * height + y;
-                out_buf[dst_idx] = channel_buf[src_idx];
+                write_sample(out_buf, dst_idx, channel_buf[src_idx], bytes_per_sample);
             }
         }
     }